@@ -1,7 +1,13 @@
 mod shader;
 
-pub use self::shader::{Shader, Program, Error};
+pub use self::shader::{Shader, Program, Error, ShaderVersion, ShaderWatcher};
 
 
 mod vertex_buffer;
 pub use self::vertex_buffer::{VertexBuffer};
+
+mod vertex_array;
+pub use self::vertex_array::{VertexArray, VertexAttribute, AttributeRef};
+
+mod uniform_buffer;
+pub use self::uniform_buffer::{UniformBuffer};
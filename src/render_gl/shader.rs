@@ -1,7 +1,9 @@
 use gl;
 use std;
 use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
 use std::ffi::{CString, CStr};
+use std::hash::{Hash, Hasher};
 use resources::{self, Resources};
 
 #[derive(Debug, Fail)]
@@ -14,41 +16,124 @@ pub enum Error {
     CompileError { name: String, message: String },
     #[fail(display = "Failed to link program {}: {}", name, message)]
     LinkError { name: String, message: String },
+    #[fail(display = "Uniform {} not found", name)]
+    UnknownUniform { name: String },
+    #[fail(display = "Uniform {} has type {}, expected {}", name, actual, expected)]
+    UniformTypeMismatch { name: String, expected: gl::types::GLenum, actual: gl::types::GLenum },
+    #[fail(display = "Attribute {} not found", name)]
+    UnknownAttribute { name: String },
+    #[fail(display = "Uniform block {} not found", name)]
+    UnknownUniformBlock { name: String },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShaderVersion {
+    Glsl3,
+    Gles2,
+}
+
+impl ShaderVersion {
+    fn header(self) -> &'static str {
+        match self {
+            ShaderVersion::Glsl3 => "#version 330 core\n",
+            ShaderVersion::Gles2 => "#version 100\n#define GLES2_RENDERER\n",
+        }
+    }
 }
 
 struct Uniform {
-    id: gl::types::GLint, 
+    id: gl::types::GLint,
     typ: gl::types::GLenum,
 }
+
+struct UniformBlock {
+    index: gl::types::GLuint,
+    size: gl::types::GLint,
+}
+type LinkResult = (
+    gl::types::GLuint,
+    HashMap<String, Uniform>,
+    HashMap<String, gl::types::GLint>,
+    HashMap<String, UniformBlock>,
+);
+
+const PROGRAM_EXT: [&str; 6] = [
+    ".vert",
+    ".frag",
+    ".geom",
+    ".tesc",
+    ".tese",
+    ".comp",
+];
+
 pub struct Program {
     gl: gl::Gl,
     id: gl::types::GLuint,
 
     uniforms: HashMap<String, Uniform>,
+    attributes: HashMap<String, gl::types::GLint>,
+    uniform_blocks: HashMap<String, UniformBlock>,
+    bound_uniform_blocks: HashMap<String, gl::types::GLuint>,
+
+    res_name: String,
+    version: ShaderVersion,
 }
 
 impl Program {
-    pub fn from_res(gl: &gl::Gl, res: &Resources, name: &str) -> Result<Program, Error> {
-        const POSSIBLE_EXT: [&str; 2] = [
-            ".vert",
-            ".frag",
-        ];
-
-        let resource_names = POSSIBLE_EXT.iter()
-            .map(|file_extension| format!("{}{}", name, file_extension))
-            .collect::<Vec<String>>();
+    pub fn from_res(gl: &gl::Gl, res: &Resources, name: &str, version: ShaderVersion) -> Result<Program, Error> {
+        let shaders = Program::load_stage_shaders(gl, res, name, version)?;
+
+        let (id, uniforms, attributes, uniform_blocks) = Program::link_shaders(gl, &shaders[..])
+            .map_err(|message| Error::LinkError { name: name.into(), message })?;
+
+        Ok(Program {
+            gl: gl.clone(),
+            id,
+            uniforms,
+            attributes,
+            uniform_blocks,
+            bound_uniform_blocks: HashMap::new(),
+            res_name: name.into(),
+            version,
+        })
+    }
 
-        let shaders = resource_names.iter()
-            .map(|resource_name| {
-                Shader::from_res(gl, res, resource_name)
+    fn load_stage_shaders(gl: &gl::Gl, res: &Resources, name: &str, version: ShaderVersion) -> Result<Vec<Shader>, Error> {
+        PROGRAM_EXT.iter()
+            .filter_map(|file_extension| {
+                let resource_name = format!("{}{}", name, file_extension);
+                match Shader::from_res(gl, res, &resource_name, version) {
+                    Ok(shader) => Some(Ok(shader)),
+                    Err(Error::ResourceLoad { .. }) => None,
+                    Err(e) => Some(Err(e)),
+                }
             })
-            .collect::<Result<Vec<Shader>, Error>>()?;
-
-        Program::from_shaders(gl, &shaders[..])
-            .map_err(|message| Error::LinkError { name: name.into(), message })
+            .collect()
     }
 
     pub fn from_shaders(gl: &gl::Gl, shaders: &[Shader]) -> Result<Program, String> {
+        let (id, uniforms, attributes, uniform_blocks) = Program::link_shaders(gl, shaders)?;
+        Ok(Program {
+            gl: gl.clone(),
+            id,
+            uniforms,
+            attributes,
+            uniform_blocks,
+            bound_uniform_blocks: HashMap::new(),
+            res_name: String::new(),
+            version: ShaderVersion::Glsl3,
+        })
+    }
+
+    fn link_shaders(gl: &gl::Gl, shaders: &[Shader]) -> Result<LinkResult, String> {
+        let has_compute = shaders.iter().any(|s| s.kind() == gl::COMPUTE_SHADER);
+        if has_compute && shaders.len() > 1 {
+            return Err("A compute program must be linked from a single compute shader".to_owned());
+        }
+        if !has_compute && !shaders.iter().any(|s| s.kind() == gl::VERTEX_SHADER) {
+            return Err("A raster program requires a vertex shader".to_owned());
+        }
+
         let program_id = unsafe { gl.CreateProgram() };
 
         for shader in shaders {
@@ -79,6 +164,7 @@ impl Program {
                 );
             }
 
+            unsafe { gl.DeleteProgram(program_id); }
             return Err(error.to_string_lossy().into_owned());
         }
 
@@ -87,7 +173,35 @@ impl Program {
         }
 
         let uniforms = Program::get_uniforms(gl, program_id);
-        Ok(Program { gl: gl.clone(), id: program_id, uniforms: uniforms})
+        let attributes = Program::get_attributes(gl, program_id);
+        let uniform_blocks = Program::get_uniform_blocks(gl, program_id);
+        Ok((program_id, uniforms, attributes, uniform_blocks))
+    }
+
+    /// Recompiles this program's shaders from `res`, swapping in the new GL program only if it links.
+    pub fn reload_from_res(&mut self, gl: &gl::Gl, res: &Resources) -> Result<(), Error> {
+        let shaders = Program::load_stage_shaders(gl, res, &self.res_name, self.version)?;
+
+        let (id, uniforms, attributes, uniform_blocks) = Program::link_shaders(gl, &shaders[..])
+            .map_err(|message| Error::LinkError { name: self.res_name.clone(), message })?;
+
+        let old_id = self.id;
+        self.id = id;
+        self.uniforms = uniforms;
+        self.attributes = attributes;
+        self.uniform_blocks = uniform_blocks;
+
+        for (name, &binding_point) in &self.bound_uniform_blocks {
+            if let Some(block) = self.uniform_blocks.get(name) {
+                unsafe {
+                    gl.UniformBlockBinding(self.id, block.index, binding_point);
+                }
+            }
+        }
+
+        unsafe { gl.DeleteProgram(old_id); }
+
+        Ok(())
     }
 
     pub fn id(&self) -> gl::types::GLuint {
@@ -142,14 +256,179 @@ impl Program {
         }
     }
 
-    pub fn set_uniform1f(&self, name: String, value: gl::types::GLfloat) -> Result<(),String> {
-        let uniform = &self.uniforms[&name];
-        if uniform.typ == gl::FLOAT {
+    fn get_attributes(gl: &gl::Gl, id: gl::types::GLuint) -> HashMap<String, gl::types::GLint> {
+        let mut attributes: HashMap<String, gl::types::GLint> = HashMap::new();
+
+        let mut total: gl::types::GLint = -1;
+        unsafe {
+            gl.GetProgramiv(id, gl::ACTIVE_ATTRIBUTES,
+                                 &mut total as *mut gl::types::GLint);
+        }
+        for a in 0..total {
+            let mut name_len: i32 = -1;
+            let mut num: i32 = -1;
+            let mut typ: gl::types::GLenum = gl::ZERO;
+            let name = create_whitespace_cstring_with_len(256 as usize);
+
             unsafe {
-                self.gl.Uniform1f(uniform.id, value);
+                gl.GetActiveAttrib(id, a as u32, 255,
+                    &mut name_len as *mut gl::types::GLint,
+                    &mut num as *mut gl::types::GLint,
+                    &mut typ as *mut gl::types::GLenum,
+                    name.as_ptr() as *mut gl::types::GLchar);
             }
-        } else {
-            Err("This uniform takes a float");
+            let loc: gl::types::GLint;
+            unsafe {
+                loc = gl.GetAttribLocation(id, name.as_ptr());
+            }
+            let name_slice: &str = name.to_str().unwrap();
+            let name_str: String = name_slice.to_owned();
+
+            attributes.insert(name_str, loc);
+        }
+        attributes
+    }
+
+    /// Resolves a declared vertex attribute name to its linker-assigned location.
+    pub fn attrib_loc(&self, name: &str) -> Result<gl::types::GLuint, Error> {
+        self.attributes.get(name)
+            .map(|&loc| loc as gl::types::GLuint)
+            .ok_or_else(|| Error::UnknownAttribute { name: name.into() })
+    }
+
+    fn get_uniform_blocks(gl: &gl::Gl, id: gl::types::GLuint) -> HashMap<String, UniformBlock> {
+        let mut blocks: HashMap<String, UniformBlock> = HashMap::new();
+
+        let mut total: gl::types::GLint = -1;
+        unsafe {
+            gl.GetProgramiv(id, gl::ACTIVE_UNIFORM_BLOCKS,
+                                 &mut total as *mut gl::types::GLint);
+        }
+        for b in 0..total {
+            let b = b as gl::types::GLuint;
+            let mut name_len: gl::types::GLint = -1;
+            unsafe {
+                gl.GetActiveUniformBlockiv(id, b, gl::UNIFORM_BLOCK_NAME_LENGTH,
+                    &mut name_len as *mut gl::types::GLint);
+            }
+
+            let name = create_whitespace_cstring_with_len(name_len as usize);
+            unsafe {
+                gl.GetActiveUniformBlockName(id, b, name_len, std::ptr::null_mut(),
+                    name.as_ptr() as *mut gl::types::GLchar);
+            }
+
+            let mut size: gl::types::GLint = 0;
+            unsafe {
+                gl.GetActiveUniformBlockiv(id, b, gl::UNIFORM_BLOCK_DATA_SIZE,
+                    &mut size as *mut gl::types::GLint);
+            }
+
+            let name_slice: &str = name.to_str().unwrap();
+            let name_str: String = name_slice.to_owned();
+
+            blocks.insert(name_str, UniformBlock { index: b, size });
+        }
+        blocks
+    }
+
+    /// Returns the data size in bytes the driver reports for the named uniform block.
+    pub fn uniform_block_size(&self, name: &str) -> Result<gl::types::GLint, Error> {
+        self.uniform_blocks.get(name)
+            .map(|block| block.size)
+            .ok_or_else(|| Error::UnknownUniformBlock { name: name.into() })
+    }
+
+    /// Binds the named uniform block to `binding_point`, matching `UniformBuffer::bind_base`.
+    pub fn bind_uniform_block(&mut self, name: &str, binding_point: gl::types::GLuint) -> Result<(), Error> {
+        let block_index = self.uniform_blocks.get(name)
+            .ok_or_else(|| Error::UnknownUniformBlock { name: name.into() })?
+            .index;
+
+        unsafe {
+            self.gl.UniformBlockBinding(self.id, block_index, binding_point);
+        }
+
+        self.bound_uniform_blocks.insert(name.into(), binding_point);
+
+        Ok(())
+    }
+
+    fn uniform_loc_checked(&self, name: &str, expected: gl::types::GLenum) -> Result<gl::types::GLint, Error> {
+        let uniform = self.uniforms.get(name)
+            .ok_or_else(|| Error::UnknownUniform { name: name.into() })?;
+
+        if uniform.typ != expected {
+            return Err(Error::UniformTypeMismatch {
+                name: name.into(),
+                expected,
+                actual: uniform.typ,
+            });
+        }
+
+        Ok(uniform.id)
+    }
+
+    pub fn set_uniform_f32(&self, name: &str, value: gl::types::GLfloat) -> Result<(), Error> {
+        let loc = self.uniform_loc_checked(name, gl::FLOAT)?;
+        unsafe {
+            self.gl.Uniform1f(loc, value);
+        }
+        Ok(())
+    }
+
+    pub fn set_uniform_vec2(&self, name: &str, value: &[gl::types::GLfloat; 2]) -> Result<(), Error> {
+        let loc = self.uniform_loc_checked(name, gl::FLOAT_VEC2)?;
+        unsafe {
+            self.gl.Uniform2f(loc, value[0], value[1]);
+        }
+        Ok(())
+    }
+
+    pub fn set_uniform_vec3(&self, name: &str, value: &[gl::types::GLfloat; 3]) -> Result<(), Error> {
+        let loc = self.uniform_loc_checked(name, gl::FLOAT_VEC3)?;
+        unsafe {
+            self.gl.Uniform3f(loc, value[0], value[1], value[2]);
+        }
+        Ok(())
+    }
+
+    pub fn set_uniform_vec4(&self, name: &str, value: &[gl::types::GLfloat; 4]) -> Result<(), Error> {
+        let loc = self.uniform_loc_checked(name, gl::FLOAT_VEC4)?;
+        unsafe {
+            self.gl.Uniform4f(loc, value[0], value[1], value[2], value[3]);
+        }
+        Ok(())
+    }
+
+    pub fn set_uniform_i32(&self, name: &str, value: gl::types::GLint) -> Result<(), Error> {
+        let loc = self.uniform_loc_checked(name, gl::INT)?;
+        unsafe {
+            self.gl.Uniform1i(loc, value);
+        }
+        Ok(())
+    }
+
+    pub fn set_uniform_mat3(&self, name: &str, value: &[gl::types::GLfloat; 9]) -> Result<(), Error> {
+        let loc = self.uniform_loc_checked(name, gl::FLOAT_MAT3)?;
+        unsafe {
+            self.gl.UniformMatrix3fv(loc, 1, gl::FALSE, value.as_ptr());
+        }
+        Ok(())
+    }
+
+    pub fn set_uniform_mat4(&self, name: &str, value: &[gl::types::GLfloat; 16]) -> Result<(), Error> {
+        let loc = self.uniform_loc_checked(name, gl::FLOAT_MAT4)?;
+        unsafe {
+            self.gl.UniformMatrix4fv(loc, 1, gl::FALSE, value.as_ptr());
+        }
+        Ok(())
+    }
+
+    pub fn set_uniform_sampler2d(&self, name: &str, texture_unit: gl::types::GLint) -> Result<(), Error> {
+        let loc = self.uniform_loc_checked(name, gl::SAMPLER_2D)?;
+        unsafe {
+            self.gl.Uniform1i(loc, texture_unit);
         }
         Ok(())
     }
@@ -163,16 +442,66 @@ impl Drop for Program {
     }
 }
 
+/// Polls a `Program`'s backing shader resources and reloads it in place when they change.
+pub struct ShaderWatcher {
+    res_name: String,
+    version: ShaderVersion,
+    source_hashes: Vec<u64>,
+}
+
+impl ShaderWatcher {
+    pub fn new(res: &Resources, program: &Program) -> ShaderWatcher {
+        let res_name = program.res_name.clone();
+        let version = program.version;
+        let source_hashes = ShaderWatcher::hash_sources(res, &res_name);
+
+        ShaderWatcher { res_name, version, source_hashes }
+    }
+
+    fn hash_sources(res: &Resources, res_name: &str) -> Vec<u64> {
+        PROGRAM_EXT.iter()
+            .map(|file_extension| {
+                let resource_name = format!("{}{}", res_name, file_extension);
+                match res.load_cstring(&resource_name) {
+                    Ok(source) => {
+                        let mut hasher = DefaultHasher::new();
+                        source.as_bytes().hash(&mut hasher);
+                        hasher.finish()
+                    },
+                    Err(_) => 0,
+                }
+            })
+            .collect()
+    }
+
+    /// Reloads `program` if any of its shader stages changed since the last poll.
+    pub fn poll(&mut self, gl: &gl::Gl, res: &Resources, program: &mut Program) -> Result<bool, Error> {
+        let hashes = ShaderWatcher::hash_sources(res, &self.res_name);
+        if hashes == self.source_hashes {
+            return Ok(false);
+        }
+
+        program.reload_from_res(gl, res)?;
+        self.source_hashes = hashes;
+        Ok(true)
+    }
+}
+
 pub struct Shader {
     gl: gl::Gl,
     id: gl::types::GLuint,
+    kind: gl::types::GLenum,
 }
 
 impl Shader {
-    pub fn from_res(gl: &gl::Gl, res: &Resources, name: &str) -> Result<Shader, Error> {
-        const POSSIBLE_EXT: [(&str, gl::types::GLenum); 2] = [
+    pub fn from_res(gl: &gl::Gl, res: &Resources, name: &str, version: ShaderVersion) -> Result<Shader, Error> {
+        const POSSIBLE_EXT: [(&str, gl::types::GLenum); 6] = [
             (".vert", gl::VERTEX_SHADER),
             (".frag", gl::FRAGMENT_SHADER),
+            (".geom", gl::GEOMETRY_SHADER),
+            (".tesc", gl::TESS_CONTROL_SHADER),
+            (".tese", gl::TESS_EVALUATION_SHADER),
+            (".comp", gl::COMPUTE_SHADER),
         ];
 
         let shader_kind = POSSIBLE_EXT.iter()
@@ -185,30 +514,35 @@ impl Shader {
         let source = res.load_cstring(name)
             .map_err(|e| Error::ResourceLoad { name: name.into(), inner: e })?;
 
-        Shader::from_source(gl, &source, shader_kind)
+        Shader::from_source(gl, &source, shader_kind, version)
             .map_err(|message| Error::CompileError { name: name.into(), message })
     }
 
     pub fn from_source(
         gl: &gl::Gl,
         source: &CStr,
-        kind: gl::types::GLenum
+        kind: gl::types::GLenum,
+        version: ShaderVersion,
     ) -> Result<Shader, String> {
-        let id = shader_from_source(gl, source, kind)?;
-        Ok(Shader { gl: gl.clone(), id })
+        let id = shader_from_source(gl, source, kind, version)?;
+        Ok(Shader { gl: gl.clone(), id, kind })
     }
 
-    pub fn from_vert_source(gl: &gl::Gl, source: &CStr) -> Result<Shader, String> {
-        Shader::from_source(gl, source, gl::VERTEX_SHADER)
+    pub fn from_vert_source(gl: &gl::Gl, source: &CStr, version: ShaderVersion) -> Result<Shader, String> {
+        Shader::from_source(gl, source, gl::VERTEX_SHADER, version)
     }
 
-    pub fn from_frag_source(gl: &gl::Gl, source: &CStr) -> Result<Shader, String> {
-        Shader::from_source(gl, source, gl::FRAGMENT_SHADER)
+    pub fn from_frag_source(gl: &gl::Gl, source: &CStr, version: ShaderVersion) -> Result<Shader, String> {
+        Shader::from_source(gl, source, gl::FRAGMENT_SHADER, version)
     }
 
     pub fn id(&self) -> gl::types::GLuint {
         self.id
     }
+
+    pub fn kind(&self) -> gl::types::GLenum {
+        self.kind
+    }
 }
 
 impl Drop for Shader {
@@ -222,11 +556,15 @@ impl Drop for Shader {
 fn shader_from_source(
     gl: &gl::Gl,
     source: &CStr,
-    kind: gl::types::GLenum
+    kind: gl::types::GLenum,
+    version: ShaderVersion,
 ) -> Result<gl::types::GLuint, String> {
+    let header = CString::new(version.header()).unwrap();
+
     let id = unsafe { gl.CreateShader(kind) };
     unsafe {
-        gl.ShaderSource(id, 1, &source.as_ptr(), std::ptr::null());
+        let sources = [header.as_ptr(), source.as_ptr()];
+        gl.ShaderSource(id, 2, sources.as_ptr(), std::ptr::null());
         gl.CompileShader(id);
     }
 
@@ -252,6 +590,7 @@ fn shader_from_source(
             );
         }
 
+        unsafe { gl.DeleteShader(id); }
         return Err(error.to_string_lossy().into_owned());
     }
 
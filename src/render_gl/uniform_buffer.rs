@@ -0,0 +1,53 @@
+use std;
+
+extern crate gl;
+
+pub struct UniformBuffer {
+    gl: gl::Gl,
+    glid: gl::types::GLuint,
+}
+
+impl UniformBuffer {
+    pub fn new_uniform_buffer(gl: &gl::Gl, data: Vec<f32>) -> UniformBuffer {
+
+        let mut ubo = UniformBuffer{
+            gl: gl.clone(),
+            glid: 0,
+        };
+
+        unsafe {
+            gl.GenBuffers(1, &mut ubo.glid);
+            gl.BindBuffer(gl::UNIFORM_BUFFER, ubo.glid);
+            gl.BufferData(
+                gl::UNIFORM_BUFFER,
+                (data.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr,
+                data.as_ptr() as *const gl::types::GLvoid,
+                gl::DYNAMIC_DRAW,
+            );
+            gl.BindBuffer(gl::UNIFORM_BUFFER, 0);
+
+        }
+
+        ubo
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            self.gl.BindBuffer(gl::UNIFORM_BUFFER, self.glid);
+        }
+    }
+
+    pub fn bind_base(&self, binding_point: gl::types::GLuint) {
+        unsafe {
+            self.gl.BindBufferBase(gl::UNIFORM_BUFFER, binding_point, self.glid);
+        }
+    }
+}
+
+impl Drop for UniformBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteBuffers(1, &self.glid);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std;
+use gl;
+use render_gl::{Program, Error, VertexBuffer};
+
+/// Either an attribute name to resolve against the `Program`, or an explicit location.
+pub enum AttributeRef {
+    Name(&'static str),
+    Location(gl::types::GLuint),
+}
+
+pub struct VertexAttribute {
+    pub attribute: AttributeRef,
+    pub components: gl::types::GLint,
+    pub gl_type: gl::types::GLenum,
+    pub normalized: bool,
+}
+
+pub struct VertexArray {
+    gl: gl::Gl,
+    vao: gl::types::GLuint,
+}
+
+impl VertexArray {
+    pub fn new(
+        gl: &gl::Gl,
+        program: &Program,
+        vbo: &VertexBuffer,
+        layout: &[VertexAttribute],
+    ) -> Result<VertexArray, Error> {
+        let locations = layout.iter()
+            .map(|attr| match attr.attribute {
+                AttributeRef::Name(name) => program.attrib_loc(name),
+                AttributeRef::Location(loc) => Ok(loc),
+            })
+            .collect::<Result<Vec<gl::types::GLuint>, Error>>()?;
+
+        let stride: gl::types::GLsizei = layout.iter()
+            .map(|attr| attr.components * gl_type_size(attr.gl_type))
+            .sum();
+
+        let mut vao: gl::types::GLuint = 0;
+        unsafe {
+            gl.GenVertexArrays(1, &mut vao);
+            gl.BindVertexArray(vao);
+        }
+
+        vbo.bind();
+
+        let mut offset: gl::types::GLsizei = 0;
+        for (attr, location) in layout.iter().zip(locations) {
+            unsafe {
+                gl.EnableVertexAttribArray(location);
+                gl.VertexAttribPointer(
+                    location,
+                    attr.components,
+                    attr.gl_type,
+                    if attr.normalized { gl::TRUE } else { gl::FALSE },
+                    stride,
+                    offset as *const std::os::raw::c_void,
+                );
+            }
+            offset += attr.components * gl_type_size(attr.gl_type);
+        }
+
+        unsafe {
+            gl.BindVertexArray(0);
+        }
+
+        Ok(VertexArray { gl: gl.clone(), vao })
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            self.gl.BindVertexArray(self.vao);
+        }
+    }
+}
+
+impl Drop for VertexArray {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+fn gl_type_size(gl_type: gl::types::GLenum) -> gl::types::GLsizei {
+    match gl_type {
+        gl::FLOAT => std::mem::size_of::<f32>() as gl::types::GLsizei,
+        gl::INT | gl::UNSIGNED_INT => std::mem::size_of::<i32>() as gl::types::GLsizei,
+        gl::SHORT | gl::UNSIGNED_SHORT => std::mem::size_of::<i16>() as gl::types::GLsizei,
+        gl::BYTE | gl::UNSIGNED_BYTE => std::mem::size_of::<i8>() as gl::types::GLsizei,
+        _ => panic!("unsupported vertex attribute type {}", gl_type),
+    }
+}